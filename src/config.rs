@@ -0,0 +1,121 @@
+use esp_idf_svc::nvs::{EspNvs, NvsDefault};
+use log::*;
+
+// NVS namespace the runtime configuration is stored under.
+const NVS_NAMESPACE: &str = "iot_esp32";
+
+// Compile-time defaults, used whenever a key is absent from NVS (e.g. first boot).
+const WIFI_SSID: &str = env!("WIFI_SSID");
+const WIFI_PASSWORD: &str = env!("WIFI_PASSWORD");
+const MQTT_BROKER: &str = env!("MQTT_BROKER");
+const MQTT_COMMAND_TOPIC: &str = env!("MQTT_COMMAND_TOPIC");
+const MQTT_RESPONSE_TOPIC: &str = env!("MQTT_RESPONSE_TOPIC");
+const MEASURE_INTERVAL_MS: &str = env!("MEASURE_INTERVAL_MS");
+
+// Keys the `config:<key>,<value>` MQTT command is allowed to target.
+pub const KEY_WIFI_SSID: &str = "wifi_ssid";
+pub const KEY_WIFI_PASSWORD: &str = "wifi_password";
+pub const KEY_MQTT_BROKER: &str = "mqtt_broker";
+pub const KEY_MQTT_COMMAND_TOPIC: &str = "mqtt_command_topic";
+pub const KEY_MQTT_RESPONSE_TOPIC: &str = "mqtt_response_topic";
+
+// Not part of `VALID_KEYS`: persisted via the dedicated `interval:<ms>` command instead
+// of the generic `config:` one, since it takes effect immediately rather than on reboot.
+const KEY_MEASURE_INTERVAL_MS: &str = "measure_interval_ms";
+
+/// Runtime WiFi/MQTT configuration, loaded from NVS with compile-time fallbacks.
+pub struct DeviceConfig {
+    pub wifi_ssid: String,
+    pub wifi_password: String,
+    pub mqtt_broker: String,
+    pub mqtt_command_topic: String,
+    pub mqtt_response_topic: String,
+    // 0 disables autonomous periodic measurement.
+    pub measure_interval_ms: u64,
+}
+
+impl DeviceConfig {
+    /// Opens the `iot_esp32` NVS namespace and loads every key, falling back to the
+    /// compiled-in `env!` constants when a key has never been written.
+    pub fn load(nvs_partition: EspNvsPartitionDefault) -> Self {
+        let nvs = open_nvs(nvs_partition);
+        Self {
+            wifi_ssid: get_or_default(&nvs, KEY_WIFI_SSID, WIFI_SSID),
+            wifi_password: get_or_default(&nvs, KEY_WIFI_PASSWORD, WIFI_PASSWORD),
+            mqtt_broker: get_or_default(&nvs, KEY_MQTT_BROKER, MQTT_BROKER),
+            mqtt_command_topic: get_or_default(&nvs, KEY_MQTT_COMMAND_TOPIC, MQTT_COMMAND_TOPIC),
+            mqtt_response_topic: get_or_default(&nvs, KEY_MQTT_RESPONSE_TOPIC, MQTT_RESPONSE_TOPIC),
+            measure_interval_ms: get_or_default(&nvs, KEY_MEASURE_INTERVAL_MS, MEASURE_INTERVAL_MS)
+                .parse()
+                .unwrap_or(0),
+        }
+    }
+}
+
+type EspNvsPartitionDefault = esp_idf_svc::nvs::EspNvsPartition<NvsDefault>;
+
+fn open_nvs(nvs_partition: EspNvsPartitionDefault) -> EspNvs<NvsDefault> {
+    EspNvs::new(nvs_partition, NVS_NAMESPACE, true).expect("Failed to open NVS namespace")
+}
+
+fn get_or_default(nvs: &EspNvs<NvsDefault>, key: &str, default: &str) -> String {
+    let mut buf = [0u8; 256];
+    match nvs.get_str(key, &mut buf) {
+        Ok(Some(value)) => value.to_owned(),
+        Ok(None) => default.to_owned(),
+        Err(e) => {
+            warn!("Failed to read NVS key {key}, using default ({e})");
+            default.to_owned()
+        }
+    }
+}
+
+/// Removes the stored WiFi credentials, e.g. when the provisioning button is held at boot.
+pub fn erase_wifi(nvs_partition: EspNvsPartitionDefault) {
+    let mut nvs = open_nvs(nvs_partition);
+    let _ = nvs.remove(KEY_WIFI_SSID);
+    let _ = nvs.remove(KEY_WIFI_PASSWORD);
+}
+
+/// Persists freshly provisioned WiFi credentials, e.g. received over SmartConfig.
+pub fn save_wifi(nvs_partition: EspNvsPartitionDefault, ssid: &str, password: &str) {
+    let mut nvs = open_nvs(nvs_partition);
+    if let Err(e) = nvs.set_str(KEY_WIFI_SSID, ssid) {
+        error!("Failed to store wifi_ssid in NVS\n{e}");
+    }
+    if let Err(e) = nvs.set_str(KEY_WIFI_PASSWORD, password) {
+        error!("Failed to store wifi_password in NVS\n{e}");
+    }
+}
+
+/// Persists a single config key to NVS. Called from the `config:<key>,<value>` MQTT command.
+pub fn set(nvs_partition: EspNvsPartitionDefault, key: &str, value: &str) -> bool {
+    let mut nvs = open_nvs(nvs_partition);
+    match nvs.set_str(key, value) {
+        Ok(_) => {
+            info!("Stored new value for {key} in NVS");
+            true
+        }
+        Err(e) => {
+            error!("Failed to store {key} in NVS\n{e}");
+            false
+        }
+    }
+}
+
+/// Persists the autonomous measurement interval so it survives a reboot. Called from the
+/// `interval:<ms>` / `interval:off` MQTT commands.
+pub fn save_measure_interval(nvs_partition: EspNvsPartitionDefault, interval_ms: u64) {
+    let mut nvs = open_nvs(nvs_partition);
+    if let Err(e) = nvs.set_str(KEY_MEASURE_INTERVAL_MS, &interval_ms.to_string()) {
+        error!("Failed to store {KEY_MEASURE_INTERVAL_MS} in NVS\n{e}");
+    }
+}
+
+pub const VALID_KEYS: [&str; 5] = [
+    KEY_WIFI_SSID,
+    KEY_WIFI_PASSWORD,
+    KEY_MQTT_BROKER,
+    KEY_MQTT_COMMAND_TOPIC,
+    KEY_MQTT_RESPONSE_TOPIC,
+];
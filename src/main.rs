@@ -7,39 +7,50 @@ use esp_idf_svc::{
     eventloop::{EspEventLoop, System, EspSystemEventLoop},
     hal::{
         adc::{attenuation, AdcChannelDriver, AdcDriver, config::Config},
+        gpio::{Pull, PinDriver},
+        i2c::{I2cConfig, I2cDriver},
+        units::FromValueType,
         peripherals::Peripherals, modem, peripheral::Peripheral
     },
     nvs::{EspDefaultNvsPartition, EspNvsPartition, NvsDefault},
     wifi::{Configuration, EspWifi, ClientConfiguration, AuthMethod, BlockingWifi},
-    mqtt::client::{EspMqttClient, MqttClientConfiguration, EspMqttConnection, QoS,
+    mqtt::client::{EspMqttClient, MqttClientConfiguration, EspMqttConnection, LwtConfiguration, QoS,
             EventPayload::{Connected, Published, Received, Subscribed}
     },
+    sntp::{EspSntp, SntpConf, SyncStatus},
+    timer::{EspTimer, EspTimerService},
     sys::EspError
 };
 use esp_idf_svc::hal::adc::ADC1;
 use esp_idf_svc::hal::gpio::Gpio34;
 use log::*;
 
-// WiFi
-const WIFI_SSID: &str = env!("WIFI_SSID");
-const WIFI_PASSWORD: &str = env!("WIFI_PASSWORD");
+mod config;
+mod provisioning;
+mod sensor;
+mod storage;
+use config::DeviceConfig;
+use sensor::{Sensor, SensorKind, Sensors, Stats};
 
 // MQTT
-const MQTT_BROKER: &str = env!("MQTT_BROKER");
-const MQTT_COMMAND_TOPIC: &str = env!("MQTT_COMMAND_TOPIC");
-const MQTT_RESPONSE_TOPIC: &str = env!("MQTT_RESPONSE_TOPIC");
 const MQTT_CLIENT_ID: &str = "ESP32";
+const MQTT_PSK_IDENTITY: &str = env!("MQTT_PSK_IDENTITY");
+const MQTT_PSK_KEY: &str = env!("MQTT_PSK_KEY");
+// Marker message the event thread sends itself through the command channel on (re)connect,
+// so the single owner of `mqtt_client` can re-subscribe after a network blip.
+const MQTT_RECONNECTED_MARKER: &str = "__mqtt_reconnected__";
 
-// Values used for the temperature calculation
-const T_1: f32 = 0.0;       // Min temp
-const T_2: f32 = 50.0;      // Max temp
-const V_1: f32 = 2100.0;    // Voltage at max temp
-const V_2: f32 = 1558.0;    // Voltage at min temp
+// SNTP
+const NTP_SERVER: &str = env!("NTP_SERVER");
 
-const V_T: f32 = (V_2 - V_1) / (T_2 - T_1); // Constant value based on the min and max
+// Sensor backend used by a "measure" command that does not name one explicitly
+const SENSOR_BACKEND: &str = env!("SENSOR_BACKEND");
 
-fn calc_temp(voltage: f32) -> f32 {
-    ((voltage - V_1) / V_T) + T_1
+fn default_sensor_kind() -> SensorKind {
+    SensorKind::from_name(SENSOR_BACKEND).unwrap_or_else(|| {
+        error!("Unknown SENSOR_BACKEND {:?}, falling back to adc", SENSOR_BACKEND);
+        SensorKind::Adc
+    })
 }
 
 fn main() {
@@ -61,6 +72,15 @@ fn main() {
     let event_loop = EspSystemEventLoop::take().unwrap();
     let nvs = EspDefaultNvsPartition::take().unwrap();
 
+    // Load WiFi/MQTT configuration from NVS, falling back to the compiled-in defaults
+    let mut cfg = DeviceConfig::load(nvs.clone());
+
+    // Mount the on-flash buffer used to hold readings while WiFi/MQTT is unavailable
+    if let Err(e) = storage::mount() {
+        error!("Failed to mount FAT storage\n{e}");
+        return
+    }
+
     // Setup ADC1 on pin GPIO34
     let (adc1, pin34) =
         match setup_adc(peripherals.adc1, peripherals.pins.gpio34) {
@@ -71,17 +91,81 @@ fn main() {
             }
         };
 
-    // Setup WiFi connection
-    let _wifi = match setup_wifi(peripherals.modem, event_loop, nvs) {
+    // Setup the I2C bus for the SHT21 (SDA/GPIO21, SCL/GPIO22)
+    let i2c = match setup_i2c(peripherals.i2c0, peripherals.pins.gpio21, peripherals.pins.gpio22) {
+        Ok(i2c) => i2c,
+        Err(e) => {
+            error!("Failed to enable I2C0 for the SHT21\n{e}");
+            return
+        }
+    };
+
+    // Every enabled sensor backend, selectable at runtime via "measure:amount,delay,<sensor>"
+    let ds18b20 = sensor::Ds18b20Sensor::new(peripherals.pins.gpio4);
+    if ds18b20.is_none() {
+        warn!("No DS18B20 found on GPIO4, the \"ds18b20\" measure backend is unavailable");
+    }
+    let mut sensors = Sensors {
+        adc: sensor::AdcProbe::new(adc1, pin34),
+        ds18b20,
+        sht21: sensor::Sht21Sensor::new(i2c),
+    };
+
+    // Provisioning button (active low, held at boot) forces re-provisioning even if
+    // credentials are already stored, e.g. to move the device onto a new network.
+    let mut button = PinDriver::input(peripherals.pins.gpio0).unwrap();
+    button.set_pull(Pull::Up).unwrap();
+    let provisioning_requested = provisioning::button_held(&button);
+    if provisioning_requested {
+        info!("Provisioning button held, erasing stored WiFi credentials");
+        config::erase_wifi(nvs.clone());
+        cfg.wifi_ssid.clear();
+        cfg.wifi_password.clear();
+    }
+
+    // Bring up the WiFi station driver before provisioning: esp_smartconfig_start requires
+    // the station to already be started (WIFI_EVENT_STA_START handled), and we may not have
+    // credentials to connect with yet at this point.
+    let mut wifi = match start_wifi(peripherals.modem, event_loop, nvs.clone()) {
         Ok(wifi) => wifi,
         Err(e) => {
+            error!("Failed to start WiFi station\n{e}");
+            return
+        }
+    };
+
+    // Only fall back to SmartConfig if the button was pressed or there's truly no SSID to
+    // try (neither NVS nor the compiled-in default) — a board flashed with build-time WIFI_SSID
+    // must still be able to connect on first boot without OTA provisioning.
+    if provisioning_requested || cfg.wifi_ssid.is_empty() {
+        info!("Entering SmartConfig provisioning");
+        match provisioning::provision(nvs.clone()) {
+            Ok((ssid, password)) => {
+                cfg.wifi_ssid = ssid;
+                cfg.wifi_password = password;
+            }
+            Err(e) => {
+                error!("SmartConfig provisioning failed\n{e}");
+                return
+            }
+        }
+    }
+
+    // Setup WiFi connection
+    let _wifi = match connect_wifi(&mut wifi, &cfg) {
+        Ok(()) => wifi,
+        Err(WifiError::MissingCredentials) => {
+            error!("No WiFi credentials available after provisioning");
+            return
+        }
+        Err(WifiError::Esp(e)) => {
             error!("Please check Wi-Fi ssid and password are correct\n{e}");
             return
         }
     };
 
     // Setup MQTT connection
-    let (mqtt_client, mqtt_conn) = match setup_mqtt() {
+    let (mqtt_client, mqtt_conn) = match setup_mqtt(&cfg.mqtt_broker, &cfg.mqtt_response_topic) {
         Ok((client, conn)) => (client, conn),
         Err(e) => {
             error!("Please check address to MQTT is correct\n{e}");
@@ -89,8 +173,44 @@ fn main() {
         }
     };
 
+    // Sync the wall clock over SNTP so published readings carry a real timestamp. Failure
+    // here isn't fatal: readings are still published, just timestamped with device uptime.
+    let sntp = match setup_sntp() {
+        Ok(sntp) => Some(sntp),
+        Err(e) => {
+            warn!("Failed to start SNTP, falling back to device uptime\n{e}");
+            None
+        }
+    };
+
     // Run and handle MQTT subscriptions and publications
-    handle_mqtt(start_time, adc1, pin34, mqtt_client, mqtt_conn);
+    handle_mqtt(start_time, sntp.as_ref(), nvs, &cfg, sensors, mqtt_client, mqtt_conn);
+}
+
+// Gives sync a bounded window to land before handing off to `handle_mqtt`; an unreachable
+// NTP server must not block the device from ever servicing MQTT commands. If it's still
+// pending when the window runs out, `timestamp_millis` keeps using device uptime until the
+// sync completes in the background.
+const SNTP_SYNC_RETRIES: u32 = 20; // 20 * 500ms = 10s
+const SNTP_SYNC_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+fn setup_sntp() -> Result<EspSntp<'static>, EspError> {
+    let sntp = EspSntp::new(&SntpConf {
+        servers: [NTP_SERVER],
+        ..Default::default()
+    })?;
+
+    info!("Waiting for SNTP time sync");
+    for _ in 0..SNTP_SYNC_RETRIES {
+        if sntp.get_sync_status() == SyncStatus::Completed {
+            info!("SNTP time synced");
+            return Ok(sntp);
+        }
+        thread::sleep(SNTP_SYNC_RETRY_DELAY);
+    }
+    warn!("SNTP still not synced after {}s, continuing with device uptime until it lands",
+            (SNTP_SYNC_RETRY_DELAY * SNTP_SYNC_RETRIES).as_secs());
+    Ok(sntp)
 }
 
 fn setup_adc(
@@ -104,58 +224,161 @@ fn setup_adc(
     Ok((adc1, pin34))
 }
 
-fn setup_wifi(
+fn setup_i2c(
+    i2c0: impl Peripheral<P = esp_idf_svc::hal::i2c::I2C0> + 'static,
+    sda: impl Peripheral<P = esp_idf_svc::hal::gpio::Gpio21> + 'static,
+    scl: impl Peripheral<P = esp_idf_svc::hal::gpio::Gpio22> + 'static
+) -> Result<I2cDriver<'static>, EspError> {
+    let config = I2cConfig::new().baudrate(100.kHz().into());
+    I2cDriver::new(i2c0, sda, scl, &config)
+}
+
+// Distinguishes "no WiFi credentials to try" from an actual connection failure, so `main`
+// can branch into SmartConfig provisioning instead of just aborting.
+#[derive(Debug)]
+enum WifiError {
+    MissingCredentials,
+    Esp(EspError)
+}
+
+impl From<EspError> for WifiError {
+    fn from(e: EspError) -> Self {
+        WifiError::Esp(e)
+    }
+}
+
+impl std::fmt::Display for WifiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WifiError::MissingCredentials => write!(f, "no WiFi credentials stored in NVS"),
+            WifiError::Esp(e) => write!(f, "{e}")
+        }
+    }
+}
+
+// Starts the station driver with a placeholder configuration, without connecting. SmartConfig
+// provisioning needs the station already started, and we may not have real credentials yet.
+fn start_wifi(
     modem: impl Peripheral<P = modem::Modem> + 'static,
     event_loop: EspEventLoop<System>,
-    nvs: EspNvsPartition<NvsDefault>
+    nvs: EspNvsPartition<NvsDefault>,
 ) -> Result<BlockingWifi<EspWifi<'static>>, EspError> {
     let mut wifi = BlockingWifi::wrap(
         EspWifi::new(modem, event_loop.clone(), Some(nvs)).unwrap(),
         event_loop,
     )?;
 
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration::default()))?;
+    wifi.start()?;
+    Ok(wifi)
+}
+
+// Applies the resolved credentials (from NVS or freshly provisioned) and connects.
+fn connect_wifi(
+    wifi: &mut BlockingWifi<EspWifi<'static>>,
+    cfg: &DeviceConfig
+) -> Result<(), WifiError> {
+    if cfg.wifi_ssid.is_empty() {
+        return Err(WifiError::MissingCredentials);
+    }
+
     wifi.set_configuration(&Configuration::Client(ClientConfiguration {
-        ssid: WIFI_SSID.try_into().unwrap(),
-        password: WIFI_PASSWORD.try_into().unwrap(),
+        ssid: cfg.wifi_ssid.as_str().try_into().unwrap(),
+        password: cfg.wifi_password.as_str().try_into().unwrap(),
         auth_method: AuthMethod::None,
         ..Default::default()
     }))?;
 
-    wifi.start()?;
     wifi.connect()?;
     wifi.wait_netif_up()?;
     info!("Connected to WiFi");
-    Ok(wifi)
+    Ok(())
 }
 
-fn setup_mqtt() -> Result<(EspMqttClient<'static>, EspMqttConnection), EspError> {
+fn setup_mqtt(mqtt_broker: &str, mqtt_response_topic: &str) -> Result<(EspMqttClient<'static>, EspMqttConnection), EspError> {
     let mqtt_cfg = MqttClientConfiguration {
         client_id: Some(MQTT_CLIENT_ID),
+        psk_hint_key: psk_hint_key(),
+        // Lets subscribers notice an ungraceful disconnect instead of just timing out
+        lwt: Some(LwtConfiguration {
+            topic: mqtt_response_topic,
+            payload: b"offline",
+            qos: QoS::AtLeastOnce,
+            retain: true,
+        }),
+        keep_alive_interval: Some(Duration::from_secs(30)),
+        reconnect_timeout: Some(Duration::from_secs(5)),
         ..Default::default()
     };
 
     let (mqtt_client, mqtt_conn) =
-        EspMqttClient::new(MQTT_BROKER, &mqtt_cfg)?;
+        EspMqttClient::new(mqtt_broker, &mqtt_cfg)?;
     info!("MQTT Connected");
     Ok((mqtt_client, mqtt_conn))
 }
 
+// `mqtts://` brokers authenticate the device via TLS-PSK instead of a certificate chain.
+fn psk_hint_key() -> Option<(&'static str, &'static [u8])> {
+    if MQTT_PSK_IDENTITY.is_empty() {
+        return None;
+    }
+    match decode_psk_key(MQTT_PSK_KEY) {
+        Some(key) => Some((MQTT_PSK_IDENTITY, key)),
+        None => {
+            error!("MQTT_PSK_KEY is not valid hex, connecting without TLS-PSK");
+            None
+        }
+    }
+}
+
+// MQTT_PSK_KEY is hex-encoded (see kconfig.projbuild), not the raw key bytes. `None` on an
+// odd-length or non-hex-digit string, rather than panicking on a config typo. Leaked once
+// since the decoded key needs to live for the whole device session.
+fn decode_psk_key(hex: &str) -> Option<&'static [u8]> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(Box::leak(bytes.into_boxed_slice()))
+}
+
 fn handle_mqtt(
     start_time: SystemTime,
-    mut adc1: AdcDriver<ADC1>,
-    mut pin34: AdcChannelDriver<{ attenuation::DB_11 }, Gpio34>,
+    sntp: Option<&EspSntp>,
+    nvs: EspNvsPartition<NvsDefault>,
+    cfg: &DeviceConfig,
+    mut sensors: Sensors,
     mut mqtt_client: EspMqttClient,
     mut mqtt_conn: EspMqttConnection
 ) {
     // Channel for sending event commands out of the MQTT thread
     let (tx, rx) = mpsc::channel::<String>();
 
+    // Periodic timer posting a "measure" job into the same channel the MQTT thread uses,
+    // so the device can report readings on its own without waiting for a controller.
+    let timer_tx = tx.clone();
+    let timer_service = EspTimerService::new().unwrap();
+    let mut measure_timer = timer_service.timer(move || {
+        timer_tx.send("measure:1,0".to_owned()).unwrap();
+    }).unwrap();
+    if cfg.measure_interval_ms > 0 {
+        measure_timer.every(Duration::from_millis(cfg.measure_interval_ms)).unwrap();
+    }
+
     // Thread for handling different MQTT events
     thread::spawn(move || {
         info!("MQTT Listening for messages");
         while let Ok(event) = mqtt_conn.next() {
             match event.payload() {
-                Connected(_) => { info!("Connected"); },
+                Connected(_) => {
+                    info!("Connected");
+                    // Re-subscribe here rather than in this thread: `mqtt_client` is owned
+                    // by the command loop below, so ask it to do the work over the channel.
+                    tx.send(MQTT_RECONNECTED_MARKER.to_owned()).unwrap();
+                },
                 Subscribed(id) => { info!("Subscribed id {}", id); },
                 Published(id) => { info!("Published id {}", id); },
                 //================================================================================//
@@ -178,7 +401,7 @@ fn handle_mqtt(
     // PHASE 1 - Subscription                                                                     //
     //============================================================================================//
 
-    mqtt_client.subscribe(MQTT_COMMAND_TOPIC, QoS::ExactlyOnce).unwrap();
+    mqtt_client.subscribe(&cfg.mqtt_command_topic, QoS::ExactlyOnce).unwrap();
 
     //============================================================================================//
     // PHASE 3 - Response                                                                         //
@@ -186,53 +409,176 @@ fn handle_mqtt(
 
     // Handle the different command from the MQTT event thread
     for x in rx { // Receive data from channel
-        let command_arr = x.split(":").collect::<Vec<&str>>();
+        // splitn(2, ..): only the command name is delimited by ':', the argument itself
+        // (e.g. a "mqtt://broker:1883" URI) may contain colons of its own.
+        let command_arr = x.splitn(2, ":").collect::<Vec<&str>>();
         if command_arr.is_empty() {
             error!("Invalid command string {:?}",x);
             continue;
         }
         match command_arr[0] {
             "measure" =>
-                handle_measure(start_time, &mut adc1, &mut pin34, &mut mqtt_client, &command_arr),
+                handle_measure(start_time, sntp, &mut sensors, &mut mqtt_client, &cfg.mqtt_response_topic, &command_arr),
+            "config" =>
+                handle_config(nvs.clone(), &command_arr),
+            "interval" =>
+                handle_interval(nvs.clone(), &mut measure_timer, &command_arr),
+            MQTT_RECONNECTED_MARKER => {
+                // The esp-mqtt client does not resubscribe on its own after a reconnect
+                mqtt_client.subscribe(&cfg.mqtt_command_topic, QoS::ExactlyOnce).unwrap();
+                info!("Re-subscribed to {} after reconnect", cfg.mqtt_command_topic);
+                // Replay whatever was buffered to flash while the connection was down
+                storage::drain(|line| {
+                    mqtt_client.publish(&cfg.mqtt_response_topic, QoS::ExactlyOnce, false, line.as_bytes()).is_ok()
+                });
+            }
             _ => error!("Unknown command {:?}", command_arr[0])
         };
     } // Command handler
 }
 
+// Persists a `config:<key>,<value>` command to NVS and reboots the device so the new
+// value is picked up on the next boot. A reboot, not an in-place reconnect, is deliberate:
+// `VALID_KEYS` includes `wifi_ssid`/`wifi_password`, and re-associating to a different
+// network needs the WiFi/MQTT stack torn down and rebuilt from scratch anyway, so rebooting
+// is the one path that applies every key uniformly. The LWT (chunk0-4) already covers
+// subscribers for this brief disconnect. `measure_interval_ms` is deliberately NOT in
+// `VALID_KEYS` for this reason — see the dedicated `interval:` command, which applies
+// immediately instead.
+fn handle_config(nvs: EspNvsPartition<NvsDefault>, command_arr: &Vec<&str>) {
+    if command_arr.len() < 2 {
+        error!("Missing args in command 'config'");
+        return;
+    }
+    let args = command_arr[1].splitn(2, ",").collect::<Vec<&str>>();
+    if args.len() != 2 {
+        error!("Wrong args amount on 'config', expected 2, got {}", args.len());
+        return;
+    }
+    let (key, value) = (args[0], args[1]);
+    if !config::VALID_KEYS.contains(&key) {
+        error!("Unknown config key {:?}", key);
+        return;
+    }
+    if config::set(nvs, key, value) {
+        info!("Config updated, rebooting to apply {key}");
+        unsafe { esp_idf_svc::sys::esp_restart(); }
+    }
+}
+
+// Starts/stops the autonomous measurement timer via `interval:<ms>` / `interval:off`,
+// persisting the new interval to NVS so it survives a reboot.
+fn handle_interval(nvs: EspNvsPartition<NvsDefault>, timer: &mut EspTimer, command_arr: &Vec<&str>) {
+    if command_arr.len() < 2 {
+        error!("Missing args in command 'interval'");
+        return;
+    }
+    if command_arr[1] == "off" {
+        timer.cancel().unwrap();
+        config::save_measure_interval(nvs, 0);
+        info!("Autonomous measurement stopped");
+        return;
+    }
+    let interval_ms: u64 = match command_arr[1].parse() {
+        Ok(ms) => ms,
+        Err(e) => {
+            error!("Failed to parse interval arg (interval:->here<-), {e}");
+            return;
+        }
+    };
+    timer.cancel().unwrap();
+    timer.every(Duration::from_millis(interval_ms)).unwrap();
+    config::save_measure_interval(nvs, interval_ms);
+    info!("Autonomous measurement interval set to {interval_ms}ms");
+}
+
 fn handle_measure(
     start_time: SystemTime,
-    adc1: &mut AdcDriver<ADC1>,
-    mut pin34: &mut AdcChannelDriver<{ attenuation::DB_11 }, Gpio34>,
+    sntp: Option<&EspSntp>,
+    sensors: &mut Sensors,
     mqtt_client: &mut EspMqttClient,
+    mqtt_response_topic: &str,
     command_arr: &Vec<&str>
 ) {
     if command_arr.len() < 2 {
         error!("Missing args in command 'measure'");
         return;
     }
-    let (amount, delay) = match parse_measure_args(command_arr[1]) {
+    let (amount, delay, sensor_kind) = match parse_measure_args(command_arr[1]) {
         Some(value) => value,
         None => return,
     };
+    let sensor = match sensors.select(sensor_kind) {
+        Some(sensor) => sensor,
+        None => {
+            error!("Sensor backend unavailable for this command");
+            return;
+        }
+    };
+
+    let mut temp_stats = Stats::new();
+    let mut humidity_stats = Stats::new();
+    let mut has_humidity = false;
+
     for i in (0..amount).rev() { // From amount to 0
         thread::sleep(Duration::from_millis(delay));
-        mqtt_client.publish(
-            MQTT_RESPONSE_TOPIC,
-            QoS::ExactlyOnce,
-            false,
-            format!("{},{:.2},{}",
-                    i, // Remaining amount
-                    calc_temp(adc1.read(&mut pin34).unwrap() as f32), // Temperature
-                    start_time.elapsed().unwrap().as_millis() // Device uptime
-            ).as_bytes()
-        ).unwrap();
+        let reading = sensor.read();
+        temp_stats.push(reading.temperature);
+        if let Some(humidity) = reading.humidity {
+            has_humidity = true;
+            humidity_stats.push(humidity);
+        }
+
+        let humidity_field = reading.humidity.map(|h| format!(",{h:.2}")).unwrap_or_default();
+        let line = format!("{},{:.2}{},{}",
+                i, // Remaining amount
+                reading.temperature,
+                humidity_field,
+                timestamp_millis(start_time, sntp) // Unix epoch ms once synced, uptime ms otherwise
+        );
+        publish_or_buffer(mqtt_client, mqtt_response_topic, &line);
+    }
+
+    if amount > 1 {
+        let humidity_summary = if has_humidity {
+            format!(",{:.2},{:.2},{:.2}", humidity_stats.min(), humidity_stats.mean(), humidity_stats.max())
+        } else {
+            String::new()
+        };
+        let summary = format!("stats,{:.2},{:.2},{:.2}{}",
+                temp_stats.min(), temp_stats.mean(), temp_stats.max(), humidity_summary);
+        publish_or_buffer(mqtt_client, mqtt_response_topic, &summary);
+    }
+}
+
+// Buffers the line to flash instead of dropping it when the broker can't be reached.
+fn publish_or_buffer(mqtt_client: &mut EspMqttClient, topic: &str, line: &str) {
+    if let Err(e) = mqtt_client.publish(topic, QoS::ExactlyOnce, false, line.as_bytes()) {
+        warn!("Publish failed, buffering reading to flash ({e})");
+        if let Err(e) = storage::append_line(line) {
+            error!("Failed to buffer reading to flash\n{e}");
+        }
     }
 }
 
-fn parse_measure_args(arg_string: &str) -> Option<(u64, u64)> {
+// Wall-clock time if SNTP has synced, device uptime otherwise (also when SNTP never
+// started at all), both in milliseconds.
+fn timestamp_millis(start_time: SystemTime, sntp: Option<&EspSntp>) -> u128 {
+    let synced = sntp.is_some_and(|sntp| sntp.get_sync_status() == SyncStatus::Completed);
+    if synced {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis()
+    } else {
+        start_time.elapsed().unwrap().as_millis()
+    }
+}
+
+fn parse_measure_args(arg_string: &str) -> Option<(u64, u64, SensorKind)> {
     let args = arg_string.split(",").collect::<Vec<&str>>();
-    if args.len() != 2 {
-        error!("Wrong args amount on 'measure', expected 2, got {}", args.len());
+    if args.len() != 2 && args.len() != 3 {
+        error!("Wrong args amount on 'measure', expected 2 or 3, got {}", args.len());
         return None;
     }
     let amount: u64 = match args[0].parse::<u64>() {
@@ -249,5 +595,15 @@ fn parse_measure_args(arg_string: &str) -> Option<(u64, u64)> {
             return None;
         }
     };
-    Some((amount, delay))
+    let sensor_kind = match args.get(2) {
+        Some(name) => match SensorKind::from_name(name) {
+            Some(kind) => kind,
+            None => {
+                error!("Unknown sensor {:?} (measure:amount,delay,->here<-)", name);
+                return None;
+            }
+        },
+        None => default_sensor_kind(),
+    };
+    Some((amount, delay, sensor_kind))
 }
\ No newline at end of file
@@ -0,0 +1,83 @@
+use std::sync::mpsc;
+use esp_idf_svc::{
+    hal::gpio::{Gpio0, Input, PinDriver},
+    nvs::{EspNvsPartition, NvsDefault},
+    sys::{self, esp, EspError}
+};
+use log::*;
+
+use crate::config;
+
+/// Pulled low by the provisioning button (active low, internal pull-up).
+pub fn button_held(pin: &PinDriver<Gpio0, Input>) -> bool {
+    pin.is_low()
+}
+
+/// Erases any stored WiFi credentials and drives SmartConfig (ESP-TOUCH) until a phone
+/// app delivers a new SSID/password pair over the air, persisting them to NVS on success.
+pub fn provision(nvs: EspNvsPartition<NvsDefault>) -> Result<(String, String), EspError> {
+    config::erase_wifi(nvs.clone());
+    let (ssid, password) = run_smartconfig()?;
+    config::save_wifi(nvs, &ssid, &password);
+    info!("SmartConfig provisioning complete");
+    Ok((ssid, password))
+}
+
+// Drives the esp-idf SmartConfig FFI directly; esp_idf_svc has no safe wrapper for it yet.
+fn run_smartconfig() -> Result<(String, String), EspError> {
+    let (tx, rx) = mpsc::channel::<sys::smartconfig_event_got_ssid_pswd_t>();
+
+    unsafe {
+        SMARTCONFIG_RESULT = Some(tx);
+
+        esp!(sys::esp_event_handler_register(
+            sys::SC_EVENT,
+            sys::smartconfig_event_t_SC_EVENT_GOT_SSID_PSWD as i32,
+            Some(on_smartconfig_event),
+            std::ptr::null_mut()
+        ))?;
+
+        esp!(sys::esp_smartconfig_set_type(sys::smartconfig_type_t_SC_TYPE_ESPTOUCH))?;
+        let mut cfg: sys::smartconfig_start_config_t = std::mem::zeroed();
+        cfg.enable_log = false;
+        esp!(sys::esp_smartconfig_start(&mut cfg))?;
+    }
+
+    // Blocks until the event handler above forwards the decoded credentials.
+    let event = rx.recv().expect("SmartConfig event channel closed unexpectedly");
+
+    unsafe {
+        sys::esp_smartconfig_stop();
+        sys::esp_event_handler_unregister(
+            sys::SC_EVENT,
+            sys::smartconfig_event_t_SC_EVENT_GOT_SSID_PSWD as i32,
+            Some(on_smartconfig_event)
+        );
+    }
+
+    let ssid = cstr_to_string(&event.ssid);
+    let password = cstr_to_string(&event.password);
+    Ok((ssid, password))
+}
+
+// Set right before `esp_smartconfig_start`, consumed once by `on_smartconfig_event`.
+static mut SMARTCONFIG_RESULT: Option<mpsc::Sender<sys::smartconfig_event_got_ssid_pswd_t>> = None;
+
+extern "C" fn on_smartconfig_event(
+    _handler_arg: *mut core::ffi::c_void,
+    _event_base: sys::esp_event_base_t,
+    _event_id: i32,
+    event_data: *mut core::ffi::c_void
+) {
+    unsafe {
+        let event = *(event_data as *const sys::smartconfig_event_got_ssid_pswd_t);
+        if let Some(tx) = SMARTCONFIG_RESULT.take() {
+            let _ = tx.send(event);
+        }
+    }
+}
+
+fn cstr_to_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
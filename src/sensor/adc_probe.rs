@@ -0,0 +1,38 @@
+use esp_idf_svc::hal::adc::{attenuation, AdcChannelDriver, AdcDriver, ADC1};
+use esp_idf_svc::hal::gpio::Gpio34;
+
+use super::{Reading, Sensor};
+
+// Calibration constants for the analog probe on GPIO34, carried over from the original
+// single-sensor firmware.
+const T_1: f32 = 0.0;       // Min temp
+const T_2: f32 = 50.0;      // Max temp
+const V_1: f32 = 2100.0;    // Voltage at max temp
+const V_2: f32 = 1558.0;    // Voltage at min temp
+const V_T: f32 = (V_2 - V_1) / (T_2 - T_1); // Constant value based on the min and max
+
+fn calc_temp(voltage: f32) -> f32 {
+    ((voltage - V_1) / V_T) + T_1
+}
+
+/// The original analog temperature probe wired to ADC1/GPIO34.
+pub struct AdcProbe<'a> {
+    adc1: AdcDriver<'a, ADC1>,
+    pin34: AdcChannelDriver<'a, { attenuation::DB_11 }, Gpio34>,
+}
+
+impl<'a> AdcProbe<'a> {
+    pub fn new(
+        adc1: AdcDriver<'a, ADC1>,
+        pin34: AdcChannelDriver<'a, { attenuation::DB_11 }, Gpio34>
+    ) -> Self {
+        Self { adc1, pin34 }
+    }
+}
+
+impl<'a> Sensor for AdcProbe<'a> {
+    fn read(&mut self) -> Reading {
+        let voltage = self.adc1.read(&mut self.pin34).unwrap() as f32;
+        Reading { temperature: calc_temp(voltage), humidity: None }
+    }
+}
@@ -0,0 +1,43 @@
+use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::hal::gpio::{AnyIOPin, Gpio4, PinDriver};
+use one_wire_bus::OneWire;
+
+use super::{Reading, Sensor};
+
+type Bus<'a> = OneWire<PinDriver<'a, AnyIOPin, esp_idf_svc::hal::gpio::InputOutput>>;
+
+/// DS18B20 on the 1-Wire bus wired to GPIO4.
+pub struct Ds18b20Sensor {
+    bus: Bus<'static>,
+    address: one_wire_bus::Address,
+}
+
+impl Ds18b20Sensor {
+    // `None` if the pin/bus can't be claimed or no DS18B20 answers the bus search, so a
+    // device with nothing wired to GPIO4 can still boot with the other sensor backends.
+    pub fn new(pin: Gpio4) -> Option<Self> {
+        let pin_driver = PinDriver::input_output_od(AnyIOPin::from(pin)).ok()?;
+        let mut bus = OneWire::new(pin_driver).ok()?;
+        let mut delay = FreeRtos;
+
+        // Assumes a single DS18B20 on the bus; search_next would be needed for more.
+        let address = bus.devices(false, &mut delay).next()?.ok()?;
+
+        Some(Self { bus, address })
+    }
+}
+
+impl Sensor for Ds18b20Sensor {
+    fn read(&mut self) -> Reading {
+        let mut delay = FreeRtos;
+        ds18b20::start_simultaneous_temp_measurement(&mut self.bus, &mut delay).unwrap();
+        ds18b20::Resolution::Bits12.delay_for_measurement_time(&mut delay);
+
+        let sensor_data = ds18b20::Ds18b20::new::<()>(self.address)
+            .unwrap()
+            .read_data(&mut self.bus, &mut delay)
+            .unwrap();
+
+        Reading { temperature: sensor_data.temperature, humidity: None }
+    }
+}
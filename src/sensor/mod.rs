@@ -0,0 +1,83 @@
+mod adc_probe;
+mod ds18b20;
+mod sht21;
+
+pub use adc_probe::AdcProbe;
+pub use ds18b20::Ds18b20Sensor;
+pub use sht21::Sht21Sensor;
+
+/// A single measurement channel. Implemented by each supported temperature/humidity backend.
+pub trait Sensor {
+    fn read(&mut self) -> Reading;
+}
+
+/// One sample. Humidity is only populated by backends that can report it.
+#[derive(Clone, Copy, Debug)]
+pub struct Reading {
+    pub temperature: f32,
+    pub humidity: Option<f32>,
+}
+
+/// Which backend a `measure:amount,delay,<sensor>` command names.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SensorKind {
+    Adc,
+    Ds18b20,
+    Sht21,
+}
+
+impl SensorKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "adc" => Some(SensorKind::Adc),
+            "ds18b20" => Some(SensorKind::Ds18b20),
+            "sht21" => Some(SensorKind::Sht21),
+            _ => None,
+        }
+    }
+}
+
+/// Holds every enabled sensor backend so a `measure` command can pick one at runtime.
+/// `ds18b20` is `None` when no device answered the 1-Wire bus search at boot, so a board
+/// without one wired up still boots and can serve `measure` for the other backends.
+pub struct Sensors<'a> {
+    pub adc: AdcProbe<'a>,
+    pub ds18b20: Option<Ds18b20Sensor>,
+    pub sht21: Sht21Sensor<'a>,
+}
+
+impl<'a> Sensors<'a> {
+    pub fn select(&mut self, kind: SensorKind) -> Option<&mut dyn Sensor> {
+        match kind {
+            SensorKind::Adc => Some(&mut self.adc),
+            SensorKind::Ds18b20 => self.ds18b20.as_mut().map(|s| s as &mut dyn Sensor),
+            SensorKind::Sht21 => Some(&mut self.sht21),
+        }
+    }
+}
+
+/// Streaming min/mean/max accumulator for a batch of readings, so `handle_measure` doesn't
+/// need to keep every sample around to report a summary.
+pub struct Stats {
+    count: u32,
+    min: f32,
+    max: f32,
+    mean: f32,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self { count: 0, min: f32::INFINITY, max: f32::NEG_INFINITY, mean: 0.0 }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.mean += (value - self.mean) / self.count as f32;
+    }
+
+    pub fn min(&self) -> f32 { self.min }
+    pub fn mean(&self) -> f32 { self.mean }
+    pub fn max(&self) -> f32 { self.max }
+}
@@ -0,0 +1,42 @@
+use esp_idf_svc::hal::delay::FreeRtos;
+use esp_idf_svc::hal::i2c::I2cDriver;
+
+use super::{Reading, Sensor};
+
+const SHT21_ADDR: u8 = 0x40;
+const CMD_TEMP_NO_HOLD: u8 = 0xF3;
+const CMD_HUMIDITY_NO_HOLD: u8 = 0xF5;
+const I2C_TIMEOUT_US: u32 = 1000;
+
+/// I2C SHT21 temperature + humidity sensor.
+pub struct Sht21Sensor<'a> {
+    i2c: I2cDriver<'a>,
+}
+
+impl<'a> Sht21Sensor<'a> {
+    pub fn new(i2c: I2cDriver<'a>) -> Self {
+        Self { i2c }
+    }
+
+    // "No hold master" mode: write the command, wait out the datasheet's worst-case
+    // conversion time, then read back the 2 data bytes + checksum.
+    fn measure_raw(&mut self, cmd: u8, conversion_delay_ms: u32) -> u16 {
+        self.i2c.write(SHT21_ADDR, &[cmd], I2C_TIMEOUT_US).unwrap();
+        FreeRtos::delay_ms(conversion_delay_ms);
+        let mut buf = [0u8; 3];
+        self.i2c.read(SHT21_ADDR, &mut buf, I2C_TIMEOUT_US).unwrap();
+        (u16::from(buf[0]) << 8 | u16::from(buf[1])) & 0xFFFC // low 2 bits are status flags
+    }
+}
+
+impl<'a> Sensor for Sht21Sensor<'a> {
+    fn read(&mut self) -> Reading {
+        let raw_temp = self.measure_raw(CMD_TEMP_NO_HOLD, 85);
+        let temperature = -46.85 + 175.72 * (raw_temp as f32) / 65536.0;
+
+        let raw_humidity = self.measure_raw(CMD_HUMIDITY_NO_HOLD, 29);
+        let humidity = -6.0 + 125.0 * (raw_humidity as f32) / 65536.0;
+
+        Reading { temperature, humidity: Some(humidity) }
+    }
+}
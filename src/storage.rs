@@ -0,0 +1,80 @@
+use std::{
+    ffi::CString,
+    fs,
+    io::{self, BufRead, Write}
+};
+use esp_idf_svc::sys::{self, esp, EspError};
+use log::*;
+
+const MOUNT_POINT: &str = "/spiflash";
+const PARTITION_LABEL: &str = "storage";
+const BUFFER_FILE: &str = "/spiflash/buffer.log";
+
+static mut WL_HANDLE: sys::wl_handle_t = sys::WL_INVALID_HANDLE;
+
+/// Mounts a FAT filesystem on the `storage` SPI flash partition (formatting it on first
+/// boot) so readings can be buffered to disk while WiFi/MQTT is down.
+pub fn mount() -> Result<(), EspError> {
+    let mount_config = sys::esp_vfs_fat_mount_config_t {
+        format_if_mount_failed: true,
+        max_files: 4,
+        allocation_unit_size: 0,
+        ..Default::default()
+    };
+    let mount_point = CString::new(MOUNT_POINT).unwrap();
+    let partition_label = CString::new(PARTITION_LABEL).unwrap();
+
+    unsafe {
+        esp!(sys::esp_vfs_fat_spiflash_mount_rw_wl(
+            mount_point.as_ptr(),
+            partition_label.as_ptr(),
+            &mount_config,
+            &mut WL_HANDLE
+        ))?;
+    }
+    info!("Mounted FAT storage at {MOUNT_POINT}");
+    Ok(())
+}
+
+/// Appends one timestamped reading line to the rolling buffer file. Called whenever
+/// `handle_measure`'s publish fails instead of dropping the reading.
+pub fn append_line(line: &str) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(BUFFER_FILE)?;
+    writeln!(file, "{line}")
+}
+
+/// Replays every buffered line through `publish`, in order, truncating the file once all
+/// lines have been acknowledged. Lines from the first failure onward are kept for the
+/// next reconnect instead of being dropped.
+pub fn drain(mut publish: impl FnMut(&str) -> bool) {
+    let file = match fs::File::open(BUFFER_FILE) {
+        Ok(file) => file,
+        Err(_) => return, // Nothing buffered
+    };
+
+    let lines: Vec<String> = io::BufReader::new(file).lines().filter_map(Result::ok).collect();
+    if lines.is_empty() {
+        return;
+    }
+
+    let mut acked = 0;
+    for line in &lines {
+        if !publish(line) {
+            break;
+        }
+        acked += 1;
+    }
+
+    if acked == lines.len() {
+        let _ = fs::remove_file(BUFFER_FILE);
+        info!("Replayed {acked} buffered readings");
+        return;
+    }
+
+    if let Ok(mut file) = fs::File::create(BUFFER_FILE) {
+        for line in &lines[acked..] {
+            let _ = writeln!(file, "{line}");
+        }
+    }
+    warn!("Replayed {acked} buffered readings, {} still pending", lines.len() - acked);
+}